@@ -0,0 +1,64 @@
+// Copyright 2017 CoreOS, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use chrono::prelude::*;
+
+#[derive(Clone, Value)]
+pub struct PullRequest {
+    pub user: User,
+    pub title: String,
+    pub body: String,
+    pub commits: Vec<Commit>,
+    pub comments: Vec<Comment>,
+}
+
+#[derive(Clone, Deserialize, Value)]
+pub struct User {
+    pub login: String,
+}
+
+#[derive(Clone, Deserialize, Value)]
+pub struct Commit {
+    pub sha: String,
+    pub commit: CommitBody,
+    pub author: User,
+    pub committer: User,
+    // SPDX-License-Identifier tokens scraped from the commit message and
+    // from the lines the commit's diff adds, so
+    // rules can validate license headers via `expr`'s `license_valid` and
+    // `license_deprecated` builtins.
+    #[serde(default)]
+    pub spdx_identifiers: Vec<String>,
+}
+
+#[derive(Clone, Deserialize, Value)]
+pub struct CommitBody {
+    pub author: Author,
+    pub committer: Author,
+    pub message: String,
+}
+
+#[derive(Clone, Deserialize, Value)]
+pub struct Author {
+    pub name: String,
+    pub email: String,
+    pub date: DateTime<Utc>,
+}
+
+#[derive(Clone, Deserialize, Value)]
+pub struct Comment {
+    pub user: User,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+}
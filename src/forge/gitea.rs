@@ -0,0 +1,324 @@
+// Copyright 2017 CoreOS, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use chrono::prelude::*;
+use config;
+use errors::*;
+use serde_json::{json, Value};
+
+use http::CachingClient;
+use license;
+use super::{Comment, Commit, CommitBody, Author, Forge, Permission, PullRequest, User};
+
+const MAX_CONCURRENT_REQUESTS: usize = 8;
+const BOT_LOGIN: &str = "tailor";
+
+#[derive(Deserialize)]
+struct RawPullRequest {
+    user: RawUser,
+    title: String,
+    body: String,
+}
+
+#[derive(Deserialize)]
+struct RawUser {
+    login: String,
+}
+
+#[derive(Deserialize)]
+struct RawCommitEnvelope {
+    sha: String,
+    commit: RawCommitBody,
+    author: RawUser,
+    committer: RawUser,
+}
+
+#[derive(Deserialize)]
+struct RawCommitBody {
+    message: String,
+    author: RawSignature,
+    committer: RawSignature,
+}
+
+#[derive(Deserialize)]
+struct RawSignature {
+    name: String,
+    email: String,
+    date: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+struct RawComment {
+    id: u64,
+    user: RawUser,
+    body: String,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+struct RawPermission {
+    permissions: RawPermissionFlags,
+}
+
+#[derive(Deserialize)]
+struct RawPermissionFlags {
+    admin: bool,
+    push: bool,
+    pull: bool,
+}
+
+pub struct GiteaForge {
+    host: String,
+    token: String,
+    http: CachingClient,
+}
+
+impl GiteaForge {
+    pub fn new(host: &str, token: &str) -> GiteaForge {
+        GiteaForge {
+            host: host.to_string(),
+            token: token.to_string(),
+            http: CachingClient::new(MAX_CONCURRENT_REQUESTS),
+        }
+    }
+
+    fn get<T>(&self, path: &str) -> Result<T>
+    where
+        T: ::serde::de::DeserializeOwned,
+    {
+        let url = format!("{}/api/v1{}", self.host, path);
+        let token = self.token.clone();
+        let request_url = url.clone();
+        self.http.get_json(&url, move |client| {
+            client.get(&request_url).header(
+                "Authorization",
+                format!("token {}", token),
+            )
+        })
+    }
+
+    fn post<T>(&self, path: &str, body: Value) -> Result<T>
+    where
+        T: ::serde::de::DeserializeOwned,
+    {
+        let url = format!("{}/api/v1{}", self.host, path);
+        let token = self.token.clone();
+        let request_url = url.clone();
+        self.http.send_json(&url, move |client| {
+            client
+                .post(&request_url)
+                .header("Authorization", format!("token {}", token))
+                .json(&body)
+        })
+    }
+
+    fn patch<T>(&self, path: &str, body: Value) -> Result<T>
+    where
+        T: ::serde::de::DeserializeOwned,
+    {
+        let url = format!("{}/api/v1{}", self.host, path);
+        let token = self.token.clone();
+        let request_url = url.clone();
+        self.http.send_json(&url, move |client| {
+            client
+                .patch(&request_url)
+                .header("Authorization", format!("token {}", token))
+                .json(&body)
+        })
+    }
+
+    // SPDX headers live in the file content a commit touches, not in its
+    // message, so pull the commit's raw unified diff and scan the lines it
+    // adds. Gitea serves this as `text/plain`, not JSON, hence `get_text`.
+    fn fetch_patch_identifiers(&self, repo: &config::Repo, sha: &str) -> Result<Vec<String>> {
+        let url = format!(
+            "{}/api/v1/repos/{}/{}/git/commits/{}.diff",
+            self.host,
+            repo.owner,
+            repo.repo,
+            sha
+        );
+        let token = self.token.clone();
+        let request_url = url.clone();
+        let diff = self.http.get_text(&url, move |client| {
+            client.get(&request_url).header(
+                "Authorization",
+                format!("token {}", token),
+            )
+        })?;
+
+        Ok(license::extract_identifiers_from_patch(&diff))
+    }
+}
+
+fn flags_to_permission(flags: &RawPermissionFlags) -> Permission {
+    if flags.admin {
+        Permission::Admin
+    } else if flags.push {
+        Permission::Write
+    } else if flags.pull {
+        Permission::Read
+    } else {
+        Permission::None
+    }
+}
+
+impl Forge for GiteaForge {
+    fn fetch_pull_request(&self, repo: &config::Repo, number: usize) -> Result<PullRequest> {
+        let pr: RawPullRequest = self.get(&format!(
+            "/repos/{}/{}/pulls/{}",
+            repo.owner,
+            repo.repo,
+            number
+        ))?;
+
+        let commits = self.fetch_commits(repo, number)?;
+        let comments = self.fetch_comments(repo, number)?;
+
+        Ok(PullRequest {
+            user: User { login: pr.user.login },
+            title: pr.title,
+            body: pr.body,
+            commits,
+            comments,
+        })
+    }
+
+    fn fetch_commits(&self, repo: &config::Repo, number: usize) -> Result<Vec<Commit>> {
+        let raw: Vec<RawCommitEnvelope> = self.get(&format!(
+            "/repos/{}/{}/pulls/{}/commits",
+            repo.owner,
+            repo.repo,
+            number
+        ))?;
+
+        let mut commits = Vec::with_capacity(raw.len());
+        for c in raw {
+            let mut identifiers = license::extract_identifiers(&c.commit.message);
+            identifiers.extend(self.fetch_patch_identifiers(repo, &c.sha)?);
+
+            commits.push(Commit {
+                sha: c.sha,
+                author: User { login: c.author.login },
+                committer: User { login: c.committer.login },
+                spdx_identifiers: identifiers,
+                commit: CommitBody {
+                    message: c.commit.message,
+                    author: Author {
+                        name: c.commit.author.name,
+                        email: c.commit.author.email,
+                        date: c.commit.author.date,
+                    },
+                    committer: Author {
+                        name: c.commit.committer.name,
+                        email: c.commit.committer.email,
+                        date: c.commit.committer.date,
+                    },
+                },
+            });
+        }
+        Ok(commits)
+    }
+
+    fn fetch_comments(&self, repo: &config::Repo, number: usize) -> Result<Vec<Comment>> {
+        let raw: Vec<RawComment> = self.get(&format!(
+            "/repos/{}/{}/issues/{}/comments",
+            repo.owner,
+            repo.repo,
+            number
+        ))?;
+
+        Ok(
+            raw.into_iter()
+                .map(|c| {
+                    Comment {
+                        user: User { login: c.user.login },
+                        body: c.body,
+                        created_at: c.created_at,
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    fn collaborator_permission(&self, repo: &config::Repo, login: &str) -> Result<Permission> {
+        let raw: RawPermission = self.get(&format!(
+            "/repos/{}/{}/collaborators/{}/permission",
+            repo.owner,
+            repo.repo,
+            login
+        ))?;
+
+        Ok(flags_to_permission(&raw.permissions))
+    }
+
+    fn post_status(
+        &self,
+        repo: &config::Repo,
+        sha: &str,
+        success: bool,
+        description: &str,
+    ) -> Result<()> {
+        let body = json!({
+            "state": if success { "success" } else { "failure" },
+            "context": "tailor",
+            "description": description,
+        });
+
+        let _: Value = self.post(
+            &format!("/repos/{}/{}/statuses/{}", repo.owner, repo.repo, sha),
+            body,
+        )?;
+        Ok(())
+    }
+
+    fn upsert_comment(&self, repo: &config::Repo, pr_number: usize, body: &str) -> Result<()> {
+        let comments: Vec<RawComment> = self.get(&format!(
+            "/repos/{}/{}/issues/{}/comments",
+            repo.owner,
+            repo.repo,
+            pr_number
+        ))?;
+
+        let existing = comments.into_iter().find(|c| c.user.login == BOT_LOGIN);
+        let payload = json!({ "body": body });
+
+        match existing {
+            Some(comment) => {
+                let _: Value = self.patch(
+                    &format!(
+                        "/repos/{}/{}/issues/comments/{}",
+                        repo.owner,
+                        repo.repo,
+                        comment.id
+                    ),
+                    payload,
+                )?;
+            }
+            None => {
+                let _: Value = self.post(
+                    &format!(
+                        "/repos/{}/{}/issues/{}/comments",
+                        repo.owner,
+                        repo.repo,
+                        pr_number
+                    ),
+                    payload,
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
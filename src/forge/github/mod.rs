@@ -0,0 +1,257 @@
+// Copyright 2017 CoreOS, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use config;
+use errors::*;
+use serde_json::{json, Value};
+
+use http::CachingClient;
+use license;
+use super::{Comment, Commit, Forge, Permission, PullRequest, User};
+
+mod graphql;
+
+const API_BASE: &str = "https://api.github.com";
+const MAX_CONCURRENT_REQUESTS: usize = 8;
+const BOT_LOGIN: &str = "tailor";
+
+#[derive(Deserialize)]
+struct RawComment {
+    id: u64,
+    user: User,
+}
+
+#[derive(Deserialize)]
+struct RawCommitDetail {
+    files: Vec<RawCommitFile>,
+}
+
+#[derive(Deserialize)]
+struct RawCommitFile {
+    #[serde(default)]
+    patch: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Collaborator {
+    permission: RawPermission,
+}
+
+#[derive(Deserialize, PartialEq)]
+enum RawPermission {
+    #[serde(rename = "admin")]
+    Admin,
+    #[serde(rename = "write")]
+    Write,
+    #[serde(rename = "read")]
+    Read,
+    #[serde(rename = "none")]
+    None,
+}
+
+impl From<RawPermission> for Permission {
+    fn from(raw: RawPermission) -> Permission {
+        match raw {
+            RawPermission::Admin => Permission::Admin,
+            RawPermission::Write => Permission::Write,
+            RawPermission::Read => Permission::Read,
+            RawPermission::None => Permission::None,
+        }
+    }
+}
+
+pub struct GithubForge {
+    token: String,
+    http: CachingClient,
+}
+
+impl GithubForge {
+    pub fn new(token: &str) -> Result<GithubForge> {
+        Ok(GithubForge {
+            token: token.to_string(),
+            http: CachingClient::new(MAX_CONCURRENT_REQUESTS),
+        })
+    }
+
+    fn get<T>(&self, path: &str) -> Result<T>
+    where
+        T: ::serde::de::DeserializeOwned,
+    {
+        let url = format!("{}{}", API_BASE, path);
+        let auth = format!("bearer {}", self.token);
+        let request_url = url.clone();
+        self.http.get_json(&url, move |client| {
+            client.get(&request_url).header(
+                "Authorization",
+                auth.clone(),
+            )
+        })
+    }
+
+    fn post<T>(&self, path: &str, body: Value) -> Result<T>
+    where
+        T: ::serde::de::DeserializeOwned,
+    {
+        let url = format!("{}{}", API_BASE, path);
+        let auth = format!("bearer {}", self.token);
+        let request_url = url.clone();
+        self.http.send_json(&url, move |client| {
+            client
+                .post(&request_url)
+                .header("Authorization", auth.clone())
+                .json(&body)
+        })
+    }
+
+    fn patch<T>(&self, path: &str, body: Value) -> Result<T>
+    where
+        T: ::serde::de::DeserializeOwned,
+    {
+        let url = format!("{}{}", API_BASE, path);
+        let auth = format!("bearer {}", self.token);
+        let request_url = url.clone();
+        self.http.send_json(&url, move |client| {
+            client
+                .patch(&request_url)
+                .header("Authorization", auth.clone())
+                .json(&body)
+        })
+    }
+
+    // SPDX headers live in the file content a commit touches, not in its
+    // message, so pull the per-file patches for `sha` and scan the lines
+    // they add. A large commit's patch may be truncated by the API; that's
+    // an acceptable gap since it would be for a human reviewer too.
+    fn fetch_patch_identifiers(&self, repo: &config::Repo, sha: &str) -> Result<Vec<String>> {
+        let detail: RawCommitDetail = self.get(&format!(
+            "/repos/{}/{}/commits/{}",
+            repo.owner,
+            repo.repo,
+            sha
+        ))?;
+
+        Ok(
+            detail
+                .files
+                .iter()
+                .flat_map(|file| {
+                    file.patch.as_ref().map_or_else(
+                        Vec::new,
+                        |patch| license::extract_identifiers_from_patch(patch),
+                    )
+                })
+                .collect(),
+        )
+    }
+}
+
+impl Forge for GithubForge {
+    fn fetch_pull_request(&self, repo: &config::Repo, number: usize) -> Result<PullRequest> {
+        graphql::fetch_pull_request(&self.http, &self.token, &repo.owner, &repo.repo, number)
+    }
+
+    fn fetch_commits(&self, repo: &config::Repo, number: usize) -> Result<Vec<Commit>> {
+        let mut commits: Vec<Commit> = self.get(&format!(
+            "/repos/{}/{}/pulls/{}/commits",
+            repo.owner,
+            repo.repo,
+            number
+        ))?;
+        for commit in &mut commits {
+            let mut identifiers = license::extract_identifiers(&commit.commit.message);
+            identifiers.extend(self.fetch_patch_identifiers(repo, &commit.sha)?);
+            commit.spdx_identifiers = identifiers;
+        }
+        Ok(commits)
+    }
+
+    fn fetch_comments(&self, repo: &config::Repo, number: usize) -> Result<Vec<Comment>> {
+        self.get(&format!(
+            "/repos/{}/{}/issues/{}/comments",
+            repo.owner,
+            repo.repo,
+            number
+        ))
+    }
+
+    fn collaborator_permission(&self, repo: &config::Repo, login: &str) -> Result<Permission> {
+        let collaborator: Collaborator = self.get(&format!(
+            "/repos/{}/{}/collaborators/{}/permission",
+            repo.owner,
+            repo.repo,
+            login
+        ))?;
+
+        Ok(collaborator.permission.into())
+    }
+
+    fn post_status(
+        &self,
+        repo: &config::Repo,
+        sha: &str,
+        success: bool,
+        description: &str,
+    ) -> Result<()> {
+        let body = json!({
+            "state": if success { "success" } else { "failure" },
+            "context": "tailor",
+            "description": description,
+        });
+
+        let _: Value = self.post(
+            &format!("/repos/{}/{}/statuses/{}", repo.owner, repo.repo, sha),
+            body,
+        )?;
+        Ok(())
+    }
+
+    fn upsert_comment(&self, repo: &config::Repo, pr_number: usize, body: &str) -> Result<()> {
+        let comments: Vec<RawComment> = self.get(&format!(
+            "/repos/{}/{}/issues/{}/comments",
+            repo.owner,
+            repo.repo,
+            pr_number
+        ))?;
+
+        let existing = comments.into_iter().find(|c| c.user.login == BOT_LOGIN);
+        let payload = json!({ "body": body });
+
+        match existing {
+            Some(comment) => {
+                let _: Value = self.patch(
+                    &format!(
+                        "/repos/{}/{}/issues/comments/{}",
+                        repo.owner,
+                        repo.repo,
+                        comment.id
+                    ),
+                    payload,
+                )?;
+            }
+            None => {
+                let _: Value = self.post(
+                    &format!(
+                        "/repos/{}/{}/issues/{}/comments",
+                        repo.owner,
+                        repo.repo,
+                        pr_number
+                    ),
+                    payload,
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
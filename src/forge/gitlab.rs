@@ -0,0 +1,315 @@
+// Copyright 2017 CoreOS, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use chrono::prelude::*;
+use config;
+use errors::*;
+use serde_json::{json, Value};
+
+use http::CachingClient;
+use license;
+use super::{Comment, Commit, CommitBody, Author, Forge, Permission, PullRequest, User};
+
+const MAX_CONCURRENT_REQUESTS: usize = 8;
+const BOT_USERNAME: &str = "tailor";
+
+#[derive(Deserialize)]
+struct RawMergeRequest {
+    author: RawUser,
+    title: String,
+    description: String,
+}
+
+#[derive(Deserialize)]
+struct RawUser {
+    username: String,
+}
+
+#[derive(Deserialize)]
+struct RawCommit {
+    id: String,
+    message: String,
+    author_name: String,
+    author_email: String,
+    authored_date: DateTime<Utc>,
+    committer_name: String,
+    committer_email: String,
+    committed_date: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+struct RawNote {
+    id: u64,
+    author: RawUser,
+    body: String,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+struct RawMember {
+    username: String,
+    access_level: u32,
+}
+
+#[derive(Deserialize)]
+struct RawDiff {
+    diff: String,
+}
+
+pub struct GitLabForge {
+    host: String,
+    token: String,
+    http: CachingClient,
+}
+
+impl GitLabForge {
+    pub fn new(host: &str, token: &str) -> GitLabForge {
+        GitLabForge {
+            host: host.to_string(),
+            token: token.to_string(),
+            http: CachingClient::new(MAX_CONCURRENT_REQUESTS),
+        }
+    }
+
+    fn project_path(&self, repo: &config::Repo) -> String {
+        format!("{}%2F{}", repo.owner, repo.repo)
+    }
+
+    fn get<T>(&self, path: &str) -> Result<T>
+    where
+        T: ::serde::de::DeserializeOwned,
+    {
+        let url = format!("{}/api/v4{}", self.host, path);
+        let token = self.token.clone();
+        let request_url = url.clone();
+        self.http.get_json(&url, move |client| {
+            client.get(&request_url).header(
+                "PRIVATE-TOKEN",
+                token.clone(),
+            )
+        })
+    }
+
+    fn post<T>(&self, path: &str, body: Value) -> Result<T>
+    where
+        T: ::serde::de::DeserializeOwned,
+    {
+        let url = format!("{}/api/v4{}", self.host, path);
+        let token = self.token.clone();
+        let request_url = url.clone();
+        self.http.send_json(&url, move |client| {
+            client
+                .post(&request_url)
+                .header("PRIVATE-TOKEN", token.clone())
+                .json(&body)
+        })
+    }
+
+    fn put<T>(&self, path: &str, body: Value) -> Result<T>
+    where
+        T: ::serde::de::DeserializeOwned,
+    {
+        let url = format!("{}/api/v4{}", self.host, path);
+        let token = self.token.clone();
+        let request_url = url.clone();
+        self.http.send_json(&url, move |client| {
+            client
+                .put(&request_url)
+                .header("PRIVATE-TOKEN", token.clone())
+                .json(&body)
+        })
+    }
+
+    // SPDX headers live in the file content a commit touches, not in its
+    // message, so pull the commit's per-file diffs and scan the lines they
+    // add.
+    fn fetch_patch_identifiers(&self, repo: &config::Repo, sha: &str) -> Result<Vec<String>> {
+        let project = self.project_path(repo);
+        let diffs: Vec<RawDiff> = self.get(&format!(
+            "/projects/{}/repository/commits/{}/diff",
+            project,
+            sha
+        ))?;
+
+        Ok(
+            diffs
+                .iter()
+                .flat_map(|d| license::extract_identifiers_from_patch(&d.diff))
+                .collect(),
+        )
+    }
+}
+
+fn access_level_to_permission(level: u32) -> Permission {
+    if level >= 50 {
+        Permission::Admin
+    } else if level >= 30 {
+        Permission::Write
+    } else if level >= 10 {
+        Permission::Read
+    } else {
+        Permission::None
+    }
+}
+
+impl Forge for GitLabForge {
+    fn fetch_pull_request(&self, repo: &config::Repo, number: usize) -> Result<PullRequest> {
+        let project = self.project_path(repo);
+        let mr: RawMergeRequest = self.get(&format!(
+            "/projects/{}/merge_requests/{}",
+            project,
+            number
+        ))?;
+
+        let commits = self.fetch_commits(repo, number)?;
+        let comments = self.fetch_comments(repo, number)?;
+
+        Ok(PullRequest {
+            user: User { login: mr.author.username },
+            title: mr.title,
+            body: mr.description,
+            commits,
+            comments,
+        })
+    }
+
+    fn fetch_commits(&self, repo: &config::Repo, number: usize) -> Result<Vec<Commit>> {
+        let project = self.project_path(repo);
+        let raw: Vec<RawCommit> = self.get(&format!(
+            "/projects/{}/merge_requests/{}/commits",
+            project,
+            number
+        ))?;
+
+        let mut commits = Vec::with_capacity(raw.len());
+        for c in raw {
+            let mut identifiers = license::extract_identifiers(&c.message);
+            identifiers.extend(self.fetch_patch_identifiers(repo, &c.id)?);
+
+            commits.push(Commit {
+                sha: c.id,
+                author: User { login: c.author_name.clone() },
+                committer: User { login: c.committer_name.clone() },
+                spdx_identifiers: identifiers,
+                commit: CommitBody {
+                    message: c.message,
+                    author: Author {
+                        name: c.author_name,
+                        email: c.author_email,
+                        date: c.authored_date,
+                    },
+                    committer: Author {
+                        name: c.committer_name,
+                        email: c.committer_email,
+                        date: c.committed_date,
+                    },
+                },
+            });
+        }
+        Ok(commits)
+    }
+
+    fn fetch_comments(&self, repo: &config::Repo, number: usize) -> Result<Vec<Comment>> {
+        let project = self.project_path(repo);
+        let raw: Vec<RawNote> = self.get(&format!(
+            "/projects/{}/merge_requests/{}/notes",
+            project,
+            number
+        ))?;
+
+        Ok(
+            raw.into_iter()
+                .map(|n| {
+                    Comment {
+                        user: User { login: n.author.username },
+                        body: n.body,
+                        created_at: n.created_at,
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    fn collaborator_permission(&self, repo: &config::Repo, login: &str) -> Result<Permission> {
+        let project = self.project_path(repo);
+        // `query` is a substring search, so it can return members other than
+        // `login` (e.g. "bob" matching "bobby"); filter to an exact
+        // username match before trusting the result.
+        let members: Vec<RawMember> = self.get(&format!(
+            "/projects/{}/members/all?query={}",
+            project,
+            login
+        ))?;
+
+        Ok(
+            members
+                .into_iter()
+                .find(|m| m.username == login)
+                .map(|m| access_level_to_permission(m.access_level))
+                .unwrap_or(Permission::None),
+        )
+    }
+
+    fn post_status(
+        &self,
+        repo: &config::Repo,
+        sha: &str,
+        success: bool,
+        description: &str,
+    ) -> Result<()> {
+        let project = self.project_path(repo);
+        let body = json!({
+            "state": if success { "success" } else { "failed" },
+            "context": "tailor",
+            "description": description,
+        });
+
+        let _: Value = self.post(&format!("/projects/{}/statuses/{}", project, sha), body)?;
+        Ok(())
+    }
+
+    fn upsert_comment(&self, repo: &config::Repo, pr_number: usize, body: &str) -> Result<()> {
+        let project = self.project_path(repo);
+        let notes: Vec<RawNote> = self.get(&format!(
+            "/projects/{}/merge_requests/{}/notes",
+            project,
+            pr_number
+        ))?;
+
+        let existing = notes.into_iter().find(|n| n.author.username == BOT_USERNAME);
+        let payload = json!({ "body": body });
+
+        match existing {
+            Some(note) => {
+                let _: Value = self.put(
+                    &format!(
+                        "/projects/{}/merge_requests/{}/notes/{}",
+                        project,
+                        pr_number,
+                        note.id
+                    ),
+                    payload,
+                )?;
+            }
+            None => {
+                let _: Value = self.post(
+                    &format!("/projects/{}/merge_requests/{}/notes", project, pr_number),
+                    payload,
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,69 @@
+// Copyright 2017 CoreOS, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use config;
+use errors::*;
+
+mod data;
+pub mod gitea;
+pub mod github;
+pub mod gitlab;
+
+pub use self::data::{Author, Comment, Commit, CommitBody, PullRequest, User};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Permission {
+    Admin,
+    Write,
+    Read,
+    None,
+}
+
+// A remote git host that can supply the host-neutral data tailor's rule
+// engine evaluates. GitHub, GitLab, and Gitea all implement this so
+// `validate_pull_request` and `find_exemptions` run unchanged against any
+// of them.
+pub trait Forge {
+    fn fetch_pull_request(&self, repo: &config::Repo, number: usize) -> Result<PullRequest>;
+    fn fetch_commits(&self, repo: &config::Repo, number: usize) -> Result<Vec<Commit>>;
+    fn fetch_comments(&self, repo: &config::Repo, number: usize) -> Result<Vec<Comment>>;
+    fn collaborator_permission(&self, repo: &config::Repo, login: &str) -> Result<Permission>;
+
+    // Posts (or updates) the commit status tailor reports for `sha`.
+    fn post_status(
+        &self,
+        repo: &config::Repo,
+        sha: &str,
+        success: bool,
+        description: &str,
+    ) -> Result<()>;
+
+    // Creates tailor's summary comment on the pull request, or replaces the
+    // body of one it already posted.
+    fn upsert_comment(&self, repo: &config::Repo, pr_number: usize, body: &str) -> Result<()>;
+}
+
+pub fn build(repo: &config::Repo) -> Result<Box<Forge>> {
+    match repo.forge {
+        config::ForgeKind::GitHub { ref token } => {
+            Ok(Box::new(github::GithubForge::new(token)?))
+        }
+        config::ForgeKind::GitLab { ref host, ref token } => {
+            Ok(Box::new(gitlab::GitLabForge::new(host, token)))
+        }
+        config::ForgeKind::Gitea { ref host, ref token } => {
+            Ok(Box::new(gitea::GiteaForge::new(host, token)))
+        }
+    }
+}
@@ -0,0 +1,404 @@
+// Copyright 2017 CoreOS, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use errors::*;
+use reqwest;
+use serde::de::DeserializeOwned;
+use serde_json::{self, Value};
+
+const MAX_RETRIES: u32 = 5;
+const MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(300);
+const BACKOFF_BASE_MILLIS: u64 = 1_000;
+
+struct CacheEntry {
+    etag: String,
+    body: Value,
+}
+
+// Bounds the number of outstanding requests a `CachingClient` will issue at
+// once, so validating many PRs in parallel doesn't trip abuse detection on
+// the forge's API.
+struct Semaphore {
+    permits: Mutex<usize>,
+    available: Condvar,
+}
+
+impl Semaphore {
+    fn new(permits: usize) -> Semaphore {
+        Semaphore {
+            permits: Mutex::new(permits),
+            available: Condvar::new(),
+        }
+    }
+
+    fn acquire(&self) {
+        let mut permits = self.permits.lock().unwrap();
+        while *permits == 0 {
+            permits = self.available.wait(permits).unwrap();
+        }
+        *permits -= 1;
+    }
+
+    fn release(&self) {
+        let mut permits = self.permits.lock().unwrap();
+        *permits += 1;
+        self.available.notify_one();
+    }
+}
+
+// A `reqwest::Client` wrapper that ETag-caches GET responses and retries
+// transient failures with rate-limit-aware exponential backoff. Forge
+// backends route their requests through this instead of calling
+// `reqwest::Client` directly so that caching and backoff stay transparent
+// to `validate_pull_request`.
+pub struct CachingClient {
+    client: reqwest::Client,
+    cache: Mutex<HashMap<String, CacheEntry>>,
+    semaphore: Semaphore,
+}
+
+impl CachingClient {
+    pub fn new(max_concurrent: usize) -> CachingClient {
+        CachingClient {
+            client: reqwest::Client::new(),
+            cache: Mutex::new(HashMap::new()),
+            semaphore: Semaphore::new(max_concurrent),
+        }
+    }
+
+    // Runs `build` to produce a request for `cache_key`, attaching
+    // `If-None-Match` when a cached ETag exists, retrying on transient
+    // failures, and reusing the cached body on a 304.
+    pub fn get_json<T, F>(&self, cache_key: &str, build: F) -> Result<T>
+    where
+        T: DeserializeOwned,
+        F: Fn(&reqwest::Client) -> reqwest::RequestBuilder,
+    {
+        self.semaphore.acquire();
+        let result = self.execute(cache_key, &build);
+        self.semaphore.release();
+        result
+    }
+
+    // Like `get_json`, but for writes (POST/PATCH/PUT): no `If-None-Match`
+    // is attached and the response is never cached, since the request
+    // isn't a repeatable GET. Still shares the same rate-limit-aware
+    // backoff, so a status or comment write that hits a secondary rate
+    // limit retries instead of failing outright.
+    pub fn send_json<T, F>(&self, label: &str, build: F) -> Result<T>
+    where
+        T: DeserializeOwned,
+        F: Fn(&reqwest::Client) -> reqwest::RequestBuilder,
+    {
+        self.semaphore.acquire();
+        let result = self.execute_write(label, &build);
+        self.semaphore.release();
+        result
+    }
+
+    fn execute<T, F>(&self, cache_key: &str, build: &F) -> Result<T>
+    where
+        T: DeserializeOwned,
+        F: Fn(&reqwest::Client) -> reqwest::RequestBuilder,
+    {
+        let mut attempt = 0;
+        loop {
+            let mut request = build(&self.client);
+            if let Some(etag) = self.cached_etag(cache_key) {
+                request = request.header("If-None-Match", etag);
+            }
+
+            let mut response = request.send().chain_err(
+                || format!("request to {} failed", cache_key),
+            )?;
+
+            if response.status() == reqwest::StatusCode::NotModified {
+                let body = self.cached_body(cache_key).ok_or_else(|| {
+                    Error::from(format!("received 304 for {} with no cached body", cache_key))
+                })?;
+                return Ok(serde_json::from_value(body)?);
+            }
+
+            if let Some(wait) =
+                retry_delay(&mut attempt, cache_key, response.status(), response.headers())?
+            {
+                thread::sleep(wait);
+                continue;
+            }
+
+            if !response.status().is_success() {
+                bail!(format!(
+                    "request to {} failed: HTTP {}",
+                    cache_key,
+                    response.status()
+                ));
+            }
+
+            let etag = response.headers().get("etag").and_then(
+                |v| v.to_str().ok().map(|s| s.to_string()),
+            );
+            let body: Value = response.json().chain_err(
+                || format!("failed to parse response from {}", cache_key),
+            )?;
+            if let Some(etag) = etag {
+                self.store(cache_key, etag, body.clone());
+            }
+
+            return Ok(serde_json::from_value(body)?);
+        }
+    }
+
+    fn execute_write<T, F>(&self, label: &str, build: &F) -> Result<T>
+    where
+        T: DeserializeOwned,
+        F: Fn(&reqwest::Client) -> reqwest::RequestBuilder,
+    {
+        let mut attempt = 0;
+        loop {
+            let mut response = build(&self.client).send().chain_err(
+                || format!("request to {} failed", label),
+            )?;
+
+            if let Some(wait) = retry_delay(&mut attempt, label, response.status(), response.headers())? {
+                thread::sleep(wait);
+                continue;
+            }
+
+            if !response.status().is_success() {
+                let status = response.status();
+                let detail = response.json::<Value>().ok().map(|body| describe_error(&body));
+                match detail {
+                    Some(detail) => {
+                        bail!(format!("request to {} failed: HTTP {} ({})", label, status, detail))
+                    }
+                    None => bail!(format!("request to {} failed: HTTP {}", label, status)),
+                }
+            }
+
+            return Ok(response.json().chain_err(
+                || format!("failed to parse response from {}", label),
+            )?);
+        }
+    }
+
+    // Like `get_json`, but for endpoints (e.g. Gitea's raw commit diff) that
+    // respond with `text/plain` instead of JSON.
+    pub fn get_text<F>(&self, cache_key: &str, build: F) -> Result<String>
+    where
+        F: Fn(&reqwest::Client) -> reqwest::RequestBuilder,
+    {
+        self.semaphore.acquire();
+        let result = self.execute_text(cache_key, &build);
+        self.semaphore.release();
+        result
+    }
+
+    fn execute_text<F>(&self, cache_key: &str, build: &F) -> Result<String>
+    where
+        F: Fn(&reqwest::Client) -> reqwest::RequestBuilder,
+    {
+        let mut attempt = 0;
+        loop {
+            let mut request = build(&self.client);
+            if let Some(etag) = self.cached_etag(cache_key) {
+                request = request.header("If-None-Match", etag);
+            }
+
+            let mut response = request.send().chain_err(
+                || format!("request to {} failed", cache_key),
+            )?;
+
+            if response.status() == reqwest::StatusCode::NotModified {
+                let body = self.cached_body(cache_key).ok_or_else(|| {
+                    Error::from(format!("received 304 for {} with no cached body", cache_key))
+                })?;
+                return Ok(body.as_str().unwrap_or_default().to_string());
+            }
+
+            if let Some(wait) =
+                retry_delay(&mut attempt, cache_key, response.status(), response.headers())?
+            {
+                thread::sleep(wait);
+                continue;
+            }
+
+            if !response.status().is_success() {
+                bail!(format!(
+                    "request to {} failed: HTTP {}",
+                    cache_key,
+                    response.status()
+                ));
+            }
+
+            let etag = response.headers().get("etag").and_then(
+                |v| v.to_str().ok().map(|s| s.to_string()),
+            );
+            let body = response.text().chain_err(
+                || format!("failed to read response from {}", cache_key),
+            )?;
+            if let Some(ref etag) = etag {
+                self.store(cache_key, etag.clone(), Value::String(body.clone()));
+            }
+
+            return Ok(body);
+        }
+    }
+
+    fn cached_etag(&self, cache_key: &str) -> Option<String> {
+        self.cache.lock().unwrap().get(cache_key).map(
+            |entry| entry.etag.clone(),
+        )
+    }
+
+    fn cached_body(&self, cache_key: &str) -> Option<Value> {
+        self.cache.lock().unwrap().get(cache_key).map(
+            |entry| entry.body.clone(),
+        )
+    }
+
+    fn store(&self, cache_key: &str, etag: String, body: Value) {
+        self.cache.lock().unwrap().insert(
+            cache_key.to_string(),
+            CacheEntry { etag, body },
+        );
+    }
+}
+
+// Inspects a response's status/headers and decides whether `execute`/
+// `execute_write` should retry it, bumping `*attempt` each time it does.
+// Returns the wait before the next attempt, or `None` if the response
+// should be handled as-is (success or a non-retryable error).
+fn retry_delay(
+    attempt: &mut u32,
+    label: &str,
+    status: reqwest::StatusCode,
+    headers: &reqwest::header::HeaderMap,
+) -> Result<Option<Duration>> {
+    // A secondary-limit 403 always carries a `Retry-After`; honor it ahead
+    // of the primary `X-RateLimit-*` headers, and count it against the
+    // retry budget so a persistent abuse signal can't spin forever.
+    if is_secondary_rate_limit(status, headers) {
+        *attempt += 1;
+        if *attempt > MAX_RETRIES {
+            bail!(format!(
+                "exhausted retries against {} after repeated secondary rate limiting",
+                label
+            ));
+        }
+        return Ok(Some(retry_after_wait(headers).unwrap_or_else(
+            || backoff_delay(*attempt),
+        )));
+    }
+
+    if let Some(wait) = rate_limit_wait(headers) {
+        *attempt += 1;
+        if *attempt > MAX_RETRIES {
+            bail!(format!(
+                "exhausted retries against {} waiting on the primary rate limit",
+                label
+            ));
+        }
+        return Ok(Some(wait));
+    }
+
+    if is_transient(status) {
+        *attempt += 1;
+        if *attempt > MAX_RETRIES {
+            bail!(format!("exhausted retries against {}: HTTP {}", label, status));
+        }
+        return Ok(Some(backoff_delay(*attempt)));
+    }
+
+    Ok(None)
+}
+
+// GitHub's error envelope is `{"message": ..., "errors": [...]}`; GitLab's
+// and Gitea's are usually just `{"message": ...}`. All three fit this
+// shape, so a failed write can surface the server's own explanation instead
+// of just an HTTP status.
+#[derive(Debug, Deserialize)]
+struct ErrorBody {
+    #[serde(default)]
+    message: Option<Value>,
+    #[serde(default)]
+    errors: Vec<Value>,
+}
+
+fn describe_error(body: &Value) -> String {
+    match serde_json::from_value::<ErrorBody>(body.clone()) {
+        Ok(ErrorBody { message, errors }) => {
+            let mut parts = Vec::new();
+            if let Some(message) = message {
+                parts.push(format!("message: {}", message));
+            }
+            if !errors.is_empty() {
+                parts.push(format!("errors: {:?}", errors));
+            }
+            if parts.is_empty() {
+                body.to_string()
+            } else {
+                parts.join(", ")
+            }
+        }
+        Err(_) => body.to_string(),
+    }
+}
+
+fn is_transient(status: reqwest::StatusCode) -> bool {
+    status.is_server_error()
+}
+
+// GitHub signals the secondary (abuse-detection) rate limit with a 403 that
+// carries `Retry-After`; a plain permission-denied 403 never does. Only the
+// former is worth retrying.
+fn is_secondary_rate_limit(status: reqwest::StatusCode, headers: &reqwest::header::HeaderMap) -> bool {
+    status == reqwest::StatusCode::Forbidden && headers.contains_key("Retry-After")
+}
+
+fn retry_after_wait(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    let seconds: u64 = headers.get("Retry-After")?.to_str().ok()?.parse().ok()?;
+    Some(Duration::from_secs(seconds).min(MAX_RATE_LIMIT_WAIT))
+}
+
+fn backoff_delay(attempt: u32) -> Duration {
+    let factor = 2u64.pow(attempt.min(6));
+    Duration::from_millis(BACKOFF_BASE_MILLIS * factor + jitter_millis())
+}
+
+fn jitter_millis() -> u64 {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().subsec_nanos();
+    u64::from(nanos % 250)
+}
+
+fn rate_limit_wait(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    let remaining = headers.get("X-RateLimit-Remaining")?.to_str().ok()?;
+    if remaining != "0" {
+        return None;
+    }
+
+    let reset: u64 = headers.get("X-RateLimit-Reset")?.to_str().ok()?.parse().ok()?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+    let wait = reset.saturating_sub(now);
+    if wait == 0 {
+        // The reset time has already passed, so this header is stale rather
+        // than a live rate-limit condition; don't treat it as one.
+        return None;
+    }
+    Some(Duration::from_secs(wait).min(MAX_RATE_LIMIT_WAIT))
+}
@@ -0,0 +1,189 @@
+// Copyright 2017 CoreOS, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, Once};
+use std::time::{Duration, Instant};
+
+use errors::*;
+use expr;
+use reqwest;
+
+const SPDX_LICENSE_LIST_URL: &str = "https://raw.githubusercontent.com/spdx/license-list-data/\
+                                      main/json/licenses.json";
+const CACHE_TTL: Duration = Duration::from_secs(3600);
+
+#[derive(Deserialize)]
+struct SpdxList {
+    #[serde(rename = "licenseListVersion")]
+    license_list_version: String,
+    licenses: Vec<SpdxLicense>,
+}
+
+#[derive(Deserialize)]
+struct SpdxLicense {
+    #[serde(rename = "licenseId")]
+    license_id: String,
+    #[serde(rename = "isDeprecatedLicenseId")]
+    is_deprecated_license_id: bool,
+}
+
+struct Cache {
+    fetched_at: Instant,
+    // The pinned version this entry was fetched for, or `None` for the
+    // unpinned/latest list. Compared against future requests so a pinned
+    // lookup never reuses a list fetched for a different (or no) pin.
+    requested_version: Option<String>,
+    licenses: HashMap<String, bool>,
+}
+
+lazy_static! {
+    static ref CACHE: Mutex<Option<Cache>> = Mutex::new(None);
+}
+
+fn list_url(pinned_version: Option<&str>) -> String {
+    match pinned_version {
+        Some(version) => {
+            format!(
+                "https://raw.githubusercontent.com/spdx/license-list-data/v{}/json/licenses.json",
+                version
+            )
+        }
+        None => SPDX_LICENSE_LIST_URL.to_string(),
+    }
+}
+
+fn fetch(pinned_version: Option<&str>) -> Result<Cache> {
+    let url = list_url(pinned_version);
+    let list: SpdxList = reqwest::get(&url)
+        .chain_err(|| format!("failed to fetch SPDX license list from {}", url))?
+        .json()
+        .chain_err(|| "failed to parse SPDX license list")?;
+
+    Ok(Cache {
+        fetched_at: Instant::now(),
+        requested_version: pinned_version.map(|v| v.to_string()),
+        licenses: list.licenses
+            .into_iter()
+            .map(|l| (l.license_id, l.is_deprecated_license_id))
+            .collect(),
+    })
+}
+
+fn with_licenses<T, F>(pinned_version: Option<&str>, f: F) -> Result<T>
+where
+    F: FnOnce(&HashMap<String, bool>) -> T,
+{
+    // Check and read a fresh entry under the same lock, so it can't be
+    // replaced by another call's fetch in between.
+    {
+        let cache = CACHE.lock().unwrap();
+        if let Some(ref entry) = *cache {
+            if entry.requested_version.as_ref().map(String::as_str) == pinned_version &&
+                entry.fetched_at.elapsed() <= CACHE_TTL
+            {
+                return Ok(f(&entry.licenses));
+            }
+        }
+    }
+
+    // Fetch outside the lock so a slow refresh doesn't block every other
+    // caller, then read the licenses straight out of the entry we just
+    // fetched rather than the shared slot: a concurrent call for a
+    // *different* `pinned_version` may have stored its own entry in between,
+    // and re-locking to read `CACHE` back could hand us that list instead of
+    // the one this call asked for.
+    let entry = fetch(pinned_version)?;
+    let result = f(&entry.licenses);
+    *CACHE.lock().unwrap() = Some(entry);
+    Ok(result)
+}
+
+// Returns `true` if `id` is a recognized SPDX license identifier.
+pub fn is_valid(id: &str, pinned_version: Option<&str>) -> Result<bool> {
+    with_licenses(pinned_version, |licenses| licenses.contains_key(id))
+}
+
+// Returns `true` if `id` is a recognized SPDX license identifier that has
+// been deprecated. Unrecognized identifiers are not considered deprecated;
+// pair with `is_valid` to catch those.
+pub fn is_deprecated(id: &str, pinned_version: Option<&str>) -> Result<bool> {
+    with_licenses(pinned_version, |licenses| {
+        licenses.get(id).cloned().unwrap_or(false)
+    })
+}
+
+static REGISTER_BUILTINS: Once = Once::new();
+
+// Registers `license_valid(id)` and `license_deprecated(id)` as `expr`
+// builtins, so rules can assert over the identifiers in
+// `commit.spdx_identifiers` without a repo-side list of known licenses.
+// Both builtins check against the unpinned/latest SPDX list. Safe to call
+// more than once; only the first call takes effect.
+pub fn register_builtins() {
+    REGISTER_BUILTINS.call_once(|| {
+        expr::register_function("license_valid", |id: String| is_valid(&id, None));
+        expr::register_function(
+            "license_deprecated",
+            |id: String| is_deprecated(&id, None),
+        );
+    });
+}
+
+// Scrapes `SPDX-License-Identifier: <expression>` tokens out of commit
+// messages and file headers so rules can assert over the licenses a pull
+// request touches. `<expression>` may combine several ids with `AND`/`OR`/
+// `WITH` and parentheses (e.g. `(MIT OR Apache-2.0)`); every id in the
+// expression is returned, not just the first.
+pub fn extract_identifiers(text: &str) -> Vec<String> {
+    const MARKER: &str = "SPDX-License-Identifier:";
+    const OPERATORS: &[&str] = &["AND", "OR", "WITH"];
+
+    text.lines()
+        .flat_map(|line| {
+            let pos = match line.find(MARKER) {
+                Some(pos) => pos,
+                None => return Vec::new(),
+            };
+
+            let mut expr = line[pos + MARKER.len()..].trim();
+            for terminator in &["*/", "-->"] {
+                if let Some(end) = expr.find(terminator) {
+                    expr = &expr[..end];
+                }
+            }
+
+            expr.split(|c: char| c == '(' || c == ')')
+                .flat_map(|s| s.split_whitespace())
+                .filter(|token| !OPERATORS.contains(token))
+                .map(|token| token.to_string())
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+// Like `extract_identifiers`, but for a unified diff (e.g. a GitHub/GitLab
+// file patch) rather than plain text: only lines the commit *added* are
+// considered, so a line removing an `SPDX-License-Identifier` header isn't
+// mistaken for one adding it.
+pub fn extract_identifiers_from_patch(patch: &str) -> Vec<String> {
+    let added: String = patch
+        .lines()
+        .filter(|line| line.starts_with('+') && !line.starts_with("+++"))
+        .map(|line| &line[1..])
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    extract_identifiers(&added)
+}
@@ -0,0 +1,51 @@
+// Copyright 2017 CoreOS, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use errors::*;
+use config;
+use forge::Forge;
+
+// Posts a commit status and upserts a summary comment on the pull request
+// so that tailor's rule outcomes are visible on the forge itself, and can
+// be wired into a required status check to block merges. Each forge backend
+// routes the writes through its own `CachingClient`, so a status or comment
+// post that hits a rate limit retries instead of failing outright.
+pub fn report_results(
+    forge: &Forge,
+    repo: &config::Repo,
+    pr_number: usize,
+    sha: &str,
+    failures: &[String],
+) -> Result<()> {
+    let description = if failures.is_empty() {
+        "All tailor rules passed".to_string()
+    } else {
+        format!("{} tailor rule(s) failed", failures.len())
+    };
+
+    forge.post_status(repo, sha, failures.is_empty(), &description)?;
+    forge.upsert_comment(repo, pr_number, &render_comment(failures))
+}
+
+fn render_comment(failures: &[String]) -> String {
+    if failures.is_empty() {
+        return "All tailor rules passed.".to_string();
+    }
+
+    let mut body = String::from("tailor found the following rule failures:\n\n");
+    for failure in failures {
+        body.push_str(&format!("- {}\n", failure));
+    }
+    body
+}